@@ -2,15 +2,18 @@
 mod log;
 mod result;
 
-use crate::result::ZippyResult;
+use crate::result::{ZippyError, ZippyResult};
 use clap::{Parser, Subcommand, ValueEnum};
-use std::env;
-use std::fs::{self, File};
+use crossbeam::channel;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, Read, Seek, Write};
 use std::iter::Iterator;
 use std::path::{Path, PathBuf};
 use std::process;
 use walkdir::{DirEntry, WalkDir};
+use zip::unstable::write::FileOptionsExt;
 use zip::{read::ZipFile, write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
@@ -54,6 +57,19 @@ enum Command {
         #[arg(required = true, short, long)]
         /// Dir path of the output directory
         output: PathBuf,
+        #[arg(long, num_args = 0..=1, default_missing_value = "")]
+        /// Password to decrypt the archive with. Pass the flag with no
+        /// value to be prompted for it interactively.
+        password: Option<String>,
+        #[arg(long, num_args = 1..)]
+        /// Only extract entries whose path matches one of these globs
+        include: Vec<String>,
+        #[arg(long, num_args = 1..)]
+        /// Skip entries whose path matches one of these globs
+        exclude: Vec<String>,
+        #[arg(long)]
+        /// Show what would be extracted without touching the filesystem
+        dry_run: bool,
     },
     #[command(arg_required_else_help = true)]
     /// Compress files and directories into a zip file.
@@ -71,9 +87,70 @@ enum Command {
         /// The compression level is dependant on which compression method
         /// is used; See the `zip-rs` documentation for more info
         level: Option<i32>,
+        #[arg(long)]
+        /// Add to `output` instead of refusing to overwrite it when it
+        /// already exists
+        append: bool,
+        #[arg(long, num_args = 0..=1, default_missing_value = "")]
+        /// Password to encrypt the archive with. Pass the flag with no
+        /// value to be prompted for it interactively.
+        password: Option<String>,
+        #[arg(short, long)]
+        /// Number of worker threads to use when compressing a directory;
+        /// defaults to the available parallelism
+        jobs: Option<usize>,
+        #[arg(long, default_value_t = 0)]
+        /// Strip this many leading path components off each entry name,
+        /// relative to the input root it came from
+        strip_components: usize,
+        #[arg(long)]
+        /// Store only the file's basename as the entry name, discarding
+        /// any directory structure
+        junk_paths: bool,
+    },
+    #[command(arg_required_else_help = true)]
+    /// List the contents of a zip file without extracting it.
+    List {
+        #[arg(required = true, short, long)]
+        /// File path of the zip archive to inspect
+        input: PathBuf,
+    },
+    #[command(arg_required_else_help = true)]
+    /// Merge several zip archives into one without recompressing entries.
+    Merge {
+        #[arg(required = true, num_args = 2.., short, long)]
+        /// File paths of the zip archives to merge, in order
+        inputs: Vec<PathBuf>,
+        #[arg(required = true, short, long)]
+        /// File path of the merged output archive
+        output: PathBuf,
+        #[arg(long, default_value = "skip")]
+        /// What to do when two inputs contain an entry with the same name
+        on_conflict: OnConflict,
+    },
+    #[command(arg_required_else_help = true)]
+    /// Compress or decompress `inputs` depending on what they look like,
+    /// without having to pick `zip` or `unzip` explicitly.
+    Auto {
+        #[arg(required = true, num_args = 1.., short, long)]
+        /// File paths to compress, or zip archives to decompress
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        /// Output archive (when compressing) or directory (when
+        /// decompressing); if omitted, the archive is named after the
+        /// first input's stem, or archives are extracted into the
+        /// current directory
+        output: Option<PathBuf>,
     },
 }
 
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
+enum OnConflict {
+    Skip,
+    Rename,
+    Overwrite,
+}
+
 #[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq)]
 enum Method {
     Bzip2,
@@ -109,28 +186,143 @@ fn main() -> ZippyResult<()> {
         }
     }
 
-    let mut zippy = Zippy::new();
-    match &cli_args.command {
-        Command::Unzip { input, output } => {
-            ensure_dir_exists(Some(output))?;
+    let mut zippy = Zippy::new(cli_args.verbosity);
+    match cli_args.command {
+        Command::Unzip { input, output, password, include, exclude, dry_run } => {
+            if !dry_run {
+                ensure_dir_exists(Some(&output))?;
+            }
             log!("unzip to directory {}", output.display());
-            zippy.unzip(input, output)?;
+            let password = resolve_password(password)?;
+            let include = build_globset(&include)?;
+            let exclude = build_globset(&exclude)?;
+            zippy.unzip(input, output, password.as_deref(), &include, &exclude, dry_run)?;
         }
-        Command::Zip { inputs, output, method, level } => {
+        Command::Zip {
+            inputs, output, method, level, append, password, jobs,
+            strip_components, junk_paths,
+        } => {
             ensure_dir_exists(output.parent())?;
             log!("zip to file @ {}", output.display());
+            let password = resolve_password(password)?;
+            let jobs = jobs.unwrap_or_else(available_parallelism);
             zippy.zip(
                 inputs.iter().map(PathBuf::as_path),
                 &output,
-                (*method).into(),
-                *level,
+                method.into(),
+                level,
+                append,
+                password.as_deref(),
+                jobs,
+                strip_components,
+                junk_paths,
             )?;
         }
+        Command::List { input } => {
+            zippy.list(input)?;
+        }
+        Command::Merge { inputs, output, on_conflict } => {
+            ensure_dir_exists(output.parent())?;
+            log!("merge {} archives into {}", inputs.len(), output.display());
+            zippy.merge(&inputs, &output, on_conflict)?;
+        }
+        Command::Auto { inputs, output } => match infer_action(&inputs, output.as_deref())? {
+            Action::Decompress => {
+                let out_dir = output.unwrap_or_else(|| PathBuf::from("."));
+                let no_filter = build_globset(&[])?;
+                for input in &inputs {
+                    let dest = if inputs.len() == 1 {
+                        out_dir.clone()
+                    } else {
+                        out_dir.join(input.file_stem().unwrap_or_default())
+                    };
+                    ensure_dir_exists(Some(&dest))?;
+                    log!("auto: unzip {} to directory {}", input.display(), dest.display());
+                    zippy.unzip(input, dest, None, &no_filter, &no_filter, false)?;
+                }
+            }
+            Action::Compress => {
+                let output = output.unwrap_or_else(|| {
+                    PathBuf::from(inputs[0].file_stem().unwrap_or_default()).with_extension("zip")
+                });
+                ensure_dir_exists(output.parent())?;
+                log!("auto: zip to file @ {}", output.display());
+                zippy.zip(
+                    inputs.iter().map(PathBuf::as_path),
+                    &output,
+                    CompressionMethod::Deflated,
+                    None,
+                    false,
+                    None,
+                    available_parallelism(),
+                    0,
+                    false,
+                )?;
+            }
+        },
     }
 
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Compress,
+    Decompress,
+}
+
+const ZIP_LOCAL_FILE_HEADER: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+const ZIP_EMPTY_ARCHIVE: [u8; 4] = [0x50, 0x4B, 0x05, 0x06];
+const ZIP_SPANNED_ARCHIVE: [u8; 4] = [0x50, 0x4B, 0x07, 0x08];
+
+/// Sniffs the first 4 bytes of `path` for a zip local-file-header,
+/// end-of-central-directory or spanned-archive signature, rather than
+/// trusting the extension.
+fn looks_like_zip(path: &Path) -> ZippyResult<bool> {
+    if path.is_dir() {
+        return Ok(false);
+    }
+    let mut magic = [0u8; 4];
+    match File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == ZIP_LOCAL_FILE_HEADER
+            || magic == ZIP_EMPTY_ARCHIVE
+            || magic == ZIP_SPANNED_ARCHIVE),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn infer_action(inputs: &[PathBuf], _output: Option<&Path>) -> ZippyResult<Action> {
+    let zip_like = inputs.iter().map(|p| looks_like_zip(p)).collect::<ZippyResult<Vec<_>>>()?;
+    match zip_like.iter().filter(|&&is_zip| is_zip).count() {
+        0 => Ok(Action::Compress),
+        n if n == inputs.len() => Ok(Action::Decompress),
+        _ => Err(ZippyError::AmbiguousOperation),
+    }
+}
+
+fn build_globset(patterns: &[String]) -> ZippyResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+fn available_parallelism() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+fn resolve_password(raw: Option<String>) -> ZippyResult<Option<String>> {
+    match raw {
+        None => Ok(None),
+        Some(pass) if pass.is_empty() => {
+            Ok(Some(rpassword::prompt_password("Password: ")?))
+        }
+        Some(pass) => Ok(Some(pass)),
+    }
+}
+
 fn ensure_dir_exists(dirpath: Option<&Path>) -> ZippyResult<()> {
     match dirpath {
         Some(dir) if dir.exists() => { /*NOP*/ }
@@ -142,11 +334,12 @@ fn ensure_dir_exists(dirpath: Option<&Path>) -> ZippyResult<()> {
 
 struct Zippy {
     buffer: Vec<u8>,
+    verbosity: u8,
 }
 
 impl Zippy {
-    pub fn new() -> Self {
-        Self { buffer: vec![] }
+    pub fn new(verbosity: u8) -> Self {
+        Self { buffer: vec![], verbosity }
     }
 
     pub fn zip<'zip>(
@@ -155,37 +348,91 @@ impl Zippy {
         output_path: &Path,
         method: CompressionMethod,
         level: Option<i32>,
+        append: bool,
+        password: Option<&str>,
+        jobs: usize,
+        strip_components: usize,
+        junk_paths: bool,
     ) -> ZippyResult<()> {
-        if output_path.exists() {
-            // TODO: addition mode i.e. open the existing
-            // zip file and add the new contents to it.
-            log!("zip file exists: {}", output_path.display());
-            process::exit(-1);
-        }
-        let mut zip: ZipWriter<_> = ZipWriter::new(File::create(output_path)?);
-        let options = FileOptions::default()
+        let (mut zip, mut seen_names): (ZipWriter<File>, HashSet<String>) = if output_path.exists() {
+            if !append {
+                log!("zip file exists: {}", output_path.display());
+                process::exit(-1);
+            }
+            let mut file = OpenOptions::new().read(true).write(true).open(output_path)?;
+            let existing = ZipArchive::new(&mut file)?;
+            let seen_names = (0..existing.len())
+                .map(|idx| existing.name_for_index(idx).expect("idx is in 0..existing.len()").to_owned())
+                .collect();
+            drop(existing);
+            let zip = ZipWriter::new_append(file)?;
+            (zip, seen_names)
+        } else {
+            (ZipWriter::new(File::create(output_path)?), HashSet::new())
+        };
+        let mut options = FileOptions::default()
             .compression_method(method)
             .unix_permissions(0o755)
             .compression_level(level);
+        if let Some(pass) = password {
+            options = options.with_deprecated_encryption(pass.as_bytes());
+        }
         for input_path in input_paths {
             // log!("input: {}", input_path.display());
             if input_path.is_dir() {
-                self.add_dir(&input_path, &mut zip, options)?;
+                let root: PathBuf = fs::canonicalize(input_path)?;
+                self.add_dir(
+                    &root, &root, &mut zip, options, &mut seen_names,
+                    jobs, strip_components, junk_paths,
+                )?;
             } else if input_path.is_file() {
-                self.add_file(&input_path, &mut zip, options)?;
+                let file_path: PathBuf = fs::canonicalize(input_path)?;
+                let root: PathBuf = file_path.parent().map(Path::to_path_buf).unwrap_or_default();
+                self.add_file(
+                    &file_path, &root, &mut zip, options, &mut seen_names,
+                    strip_components, junk_paths,
+                )?;
             } else {
                 panic!("Neither file nor directory: {}", input_path.display());
                 // TODO
             }
         }
+        zip.finish()?;
         Ok(())
     }
 
+    /// Computes the entry path for `path`, relative to `root` (the
+    /// directory argument the user gave, or a file argument's parent
+    /// directory), honoring `--strip-components`
+    /// and `--junk-paths` instead of blindly stripping the cwd.
+    fn entry_path(path: &Path, root: &Path, strip_components: usize, junk_paths: bool) -> PathBuf {
+        let relative: &Path = path.strip_prefix(root).unwrap_or(path);
+        if junk_paths {
+            return PathBuf::from(relative.file_name().unwrap_or_default());
+        }
+        let stripped: PathBuf = relative.components().skip(strip_components).collect();
+        if stripped.as_os_str().is_empty() {
+            PathBuf::from(relative.file_name().unwrap_or_default())
+        } else {
+            stripped
+        }
+    }
+
+    fn entry_name(path: &Path, root: &Path, strip_components: usize, junk_paths: bool) -> String {
+        Self::entry_path(path, root, strip_components, junk_paths)
+            .to_string_lossy()
+            .into_owned()
+    }
+
     fn add_file<W>(
         &mut self,
         input_filepath: &Path,
+        root: &Path,
         zip: &mut ZipWriter<W>,
         options: FileOptions,
+        seen_names: &mut HashSet<String>,
+        strip_components: usize,
+        junk_paths: bool,
     ) -> ZippyResult<()>
     where
         W: Write + Seek,
@@ -194,24 +441,39 @@ impl Zippy {
             panic!("Error: not a file: {}", input_filepath.display());
             // TODO
         }
-        let current_dir: PathBuf = env::current_dir()?;
-        let input_filepath: &Path = input_filepath
-            .strip_prefix(&current_dir)
-            .unwrap_or(input_filepath);
-        log!("zip {}", input_filepath.display());
-        zip.start_file(input_filepath.to_str().unwrap(/*TODO*/), options)?;
-        let mut f = File::open(&input_filepath)?;
+        let entry_name = Self::entry_name(input_filepath, root, strip_components, junk_paths);
+        if seen_names.contains(&entry_name) {
+            if self.verbosity >= 1 {
+                log!("skipping duplicate entry: {}", entry_name);
+            }
+            return Ok(());
+        }
+        log!("zip {}", entry_name);
+        zip.start_file(&entry_name, options)?;
+        let mut f = File::open(input_filepath)?;
         f.read_to_end(&mut self.buffer)?;
         zip.write_all(&*self.buffer)?;
         self.buffer.clear();
+        seen_names.insert(entry_name);
         Ok(())
     }
 
+    /// Walks `input_dirpath` and compresses its files on `jobs` worker
+    /// threads, then appends the finished entries to `zip` from this
+    /// (single) thread in sorted order, so the central directory stays
+    /// deterministic regardless of which worker finished first. Empty
+    /// directories carry no file to trigger their creation, so they are
+    /// emitted explicitly once the files are done.
     fn add_dir<W>(
         &mut self,
         input_dirpath: &Path,
+        root: &Path,
         zip: &mut ZipWriter<W>,
         options: FileOptions,
+        seen_names: &mut HashSet<String>,
+        jobs: usize,
+        strip_components: usize,
+        junk_paths: bool,
     ) -> ZippyResult<()>
     where
         W: Write + Seek,
@@ -221,41 +483,182 @@ impl Zippy {
             panic!("Error: not a directory: {}", dirpath.display());
             // TODO
         }
+
+        let mut dir_paths: Vec<PathBuf> = Vec::new();
+        let mut file_paths: Vec<PathBuf> = Vec::new();
         for entry in WalkDir::new(&dirpath) { // recursively walk `dirpath`
             let entry: DirEntry = entry?;
             let entry_path: PathBuf = fs::canonicalize(entry.path())?;
+            if entry_path == dirpath {
+                continue;
+            }
             if entry_path.is_dir() {
+                dir_paths.push(entry_path);
+            } else {
+                file_paths.push(entry_path);
+            }
+        }
+
+        // `dirpath` itself has no files and no subdirectories under it, so
+        // it won't show up as an ancestor of anything below and needs to
+        // be recorded as an empty directory in its own right.
+        let dirpath_is_empty = file_paths.is_empty() && dir_paths.is_empty();
+
+        let mut covered_dirs: HashSet<PathBuf> = HashSet::new();
+        for file_path in &file_paths {
+            for ancestor in file_path.ancestors().skip(1) {
+                if ancestor == dirpath || !covered_dirs.insert(ancestor.to_path_buf()) {
+                    break;
+                }
+            }
+        }
+        let mut empty_dirs: Vec<PathBuf> =
+            dir_paths.into_iter().filter(|d| !covered_dirs.contains(d)).collect();
+        if dirpath_is_empty {
+            empty_dirs.push(dirpath.clone());
+        }
+        empty_dirs.sort();
+
+        let (path_tx, path_rx) = channel::bounded::<PathBuf>(file_paths.len().max(1));
+        for path in file_paths {
+            path_tx.send(path).expect("worker channel closed early");
+        }
+        drop(path_tx);
+        let (entry_tx, entry_rx) = channel::unbounded::<ZippyResult<(String, Vec<u8>)>>();
+
+        let entries: ZippyResult<Vec<(String, Vec<u8>)>> = crossbeam::thread::scope(|scope| {
+            for _ in 0..jobs.max(1) {
+                let path_rx = path_rx.clone();
+                let entry_tx = entry_tx.clone();
+                scope.spawn(move |_| {
+                    for path in path_rx {
+                        let entry =
+                            Self::compress_entry(&path, root, options, strip_components, junk_paths);
+                        if entry_tx.send(entry).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(entry_tx);
+
+            let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+            for entry in entry_rx {
+                entries.push(entry?);
+            }
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Ok(entries)
+        })
+        .expect("a compression worker thread panicked");
+
+        for (entry_name, mini_zip_bytes) in entries? {
+            if seen_names.contains(&entry_name) {
+                if self.verbosity >= 1 {
+                    log!("skipping duplicate entry: {}", entry_name);
+                }
                 continue;
             }
-            self.add_file(&entry_path, zip, options)?;
+            log!("zip {}", entry_name);
+            let mut mini_zip = ZipArchive::new(io::Cursor::new(mini_zip_bytes))?;
+            zip.raw_copy_file(mini_zip.by_index_raw(0)?)?;
+            seen_names.insert(entry_name);
+        }
+
+        if !junk_paths {
+            for dir_path in empty_dirs {
+                // The root itself is relative to its own parent, not to
+                // itself (which would yield an empty, unusable name).
+                let relative = if dir_path == dirpath {
+                    PathBuf::from(dirpath.file_name().unwrap_or_default())
+                } else {
+                    Self::entry_path(&dir_path, root, strip_components, junk_paths)
+                };
+                let mut entry_name = relative.to_string_lossy().into_owned();
+                if !entry_name.ends_with('/') {
+                    entry_name.push('/');
+                }
+                if seen_names.contains(&entry_name) {
+                    continue;
+                }
+                log!("zip {}", entry_name);
+                zip.add_directory_from_path(&relative, options)?;
+                seen_names.insert(entry_name);
+            }
         }
         Ok(())
     }
 
+    /// Reads and compresses a single file into a standalone single-entry
+    /// zip buffer on whichever worker thread calls this; the entry name
+    /// and compressed bytes are handed back so the writer thread can
+    /// append them with a raw copy instead of recompressing.
+    fn compress_entry(
+        entry_path: &Path,
+        root: &Path,
+        options: FileOptions,
+        strip_components: usize,
+        junk_paths: bool,
+    ) -> ZippyResult<(String, Vec<u8>)> {
+        let entry_name = Self::entry_name(entry_path, root, strip_components, junk_paths);
+        let mut buffer = Vec::new();
+        let mut mini_zip = ZipWriter::new(io::Cursor::new(&mut buffer));
+        mini_zip.start_file(&entry_name, options)?;
+        io::copy(&mut File::open(entry_path)?, &mut mini_zip)?;
+        mini_zip.finish()?;
+        drop(mini_zip);
+        Ok((entry_name, buffer))
+    }
+
     pub fn unzip(
         &mut self,
         zip_filepath: impl AsRef<Path>,
         output_dirpath: impl AsRef<Path>,
+        password: Option<&str>,
+        include: &GlobSet,
+        exclude: &GlobSet,
+        dry_run: bool,
     ) -> ZippyResult<()> {
         let zip_filepath = zip_filepath.as_ref();
         let output_dirpath = output_dirpath.as_ref();
-        if !output_dirpath.exists() {
+        if !output_dirpath.exists() && !dry_run {
             fs::create_dir(output_dirpath)?;
             println!("[unzip] created {}", output_dirpath.display());
         }
 
         let mut archive = ZipArchive::new(File::open(&zip_filepath)?)?;
+        let mut extracted = 0;
         for i in 0..archive.len() {
-            let mut zip_file: ZipFile = archive.by_index(i)?;
+            let mut zip_file: ZipFile = match password {
+                Some(pass) => archive
+                    .by_index_decrypt(i, pass.as_bytes())?
+                    .map_err(|_| ZippyError::IncorrectPassword)?,
+                None => archive.by_index(i)?,
+            };
+            let name = zip_file.name().to_owned();
+            if (!include.is_empty() && !include.is_match(&name)) || exclude.is_match(&name) {
+                continue;
+            }
             let zip_file_name = zip_file
                 .enclosed_name()
                 .expect("Failed to extract file name from zip archive (idx: {i})");
             let output_path: PathBuf = output_dirpath.join(zip_file_name);
             Self::log_comment(i, &zip_file);
 
-            if (&*zip_file.name()).ends_with('/') {
-                println!("[unzip/{}] extracted dir {}", i, output_path.display());
-                fs::create_dir_all(&output_path)?;
+            if name.ends_with('/') {
+                if dry_run {
+                    println!("[unzip/{}] would create dir {}", i, output_path.display());
+                } else {
+                    println!("[unzip/{}] extracted dir {}", i, output_path.display());
+                    fs::create_dir_all(&output_path)?;
+                    Self::set_file_permissions(&zip_file, &output_path)?;
+                }
+            } else if dry_run {
+                println!(
+                    "[unzip/{}] would extract {} ({})",
+                    i,
+                    output_path.display(),
+                    Self::humanize(zip_file.size())
+                );
             } else {
                 if let Some(p) = output_path.parent() {
                     if !p.exists() { fs::create_dir_all(&p)?; }
@@ -268,14 +671,133 @@ impl Zippy {
                     output_path.display(),
                     Self::humanize(zip_file.size())
                 );
+                Self::set_file_permissions(&zip_file, &output_path)?;
+            }
+            extracted += 1;
+        }
+        if dry_run {
+            log!("[unzip] would extract {} files.", extracted);
+        } else {
+            log!("[unzip] extracted {} files.", extracted);
+        }
+        Ok(())
+    }
+
+    pub fn list(&mut self, zip_filepath: impl AsRef<Path>) -> ZippyResult<()> {
+        let zip_filepath = zip_filepath.as_ref();
+        let mut archive = ZipArchive::new(File::open(&zip_filepath)?)?;
+
+        let mut total_size: u64 = 0;
+        let mut total_compressed_size: u64 = 0;
+        for i in 0..archive.len() {
+            let zip_file: ZipFile = archive.by_index(i)?;
+            total_size += zip_file.size();
+            total_compressed_size += zip_file.compressed_size();
+            println!(
+                "{name}  {size}  {compressed_size}  {method:?}  {crc:08x}  {modified:?}",
+                name = zip_file.name(),
+                size = Self::humanize(zip_file.size()),
+                compressed_size = Self::humanize(zip_file.compressed_size()),
+                method = zip_file.compression(),
+                crc = zip_file.crc32(),
+                modified = zip_file.last_modified(),
+            );
+            if self.verbosity >= 1 {
+                println!(
+                    "  unix mode: {}",
+                    zip_file
+                        .unix_mode()
+                        .map(|mode| format!("{:o}", mode))
+                        .unwrap_or_else(|| String::from("n/a")),
+                );
+            }
+        }
+
+        let ratio = if total_size == 0 {
+            0.0
+        } else {
+            100.0 * (1.0 - total_compressed_size as f64 / total_size as f64)
+        };
+        println!(
+            "{} entries, {} -> {} ({:.1}% compression)",
+            archive.len(),
+            Self::humanize(total_size),
+            Self::humanize(total_compressed_size),
+            ratio,
+        );
+        Ok(())
+    }
+
+    pub fn merge(
+        &mut self,
+        input_paths: &[PathBuf],
+        output_path: &Path,
+        on_conflict: OnConflict,
+    ) -> ZippyResult<()> {
+        let mut archives: Vec<ZipArchive<File>> = input_paths
+            .iter()
+            .map(|p| Ok(ZipArchive::new(File::open(p)?)?))
+            .collect::<ZippyResult<_>>()?;
+
+        // (archive_idx, entry_idx, output_name)
+        let mut plan: Vec<(usize, usize, String)> = Vec::new();
+        let mut first_seen: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+        let mut used_output_names: HashSet<String> = HashSet::new();
+
+        for archive_idx in 0..archives.len() {
+            let len = archives[archive_idx].len();
+            for entry_idx in 0..len {
+                let name = archives[archive_idx]
+                    .name_for_index(entry_idx)
+                    .expect("entry_idx is in 0..archive.len()")
+                    .to_owned();
+                if let Some(&existing) = first_seen.get(&name) {
+                    match on_conflict {
+                        OnConflict::Skip => {
+                            if self.verbosity >= 1 {
+                                log!("merge: skipping duplicate entry {}", name);
+                            }
+                        }
+                        OnConflict::Overwrite => {
+                            plan[existing].0 = archive_idx;
+                            plan[existing].1 = entry_idx;
+                        }
+                        OnConflict::Rename => {
+                            let mut n = 2;
+                            let output_name = loop {
+                                let candidate = Self::renamed(&name, n);
+                                if !used_output_names.contains(&candidate) { break candidate; }
+                                n += 1;
+                            };
+                            used_output_names.insert(output_name.clone());
+                            plan.push((archive_idx, entry_idx, output_name));
+                        }
+                    }
+                    continue;
+                }
+                first_seen.insert(name.clone(), plan.len());
+                used_output_names.insert(name.clone());
+                plan.push((archive_idx, entry_idx, name));
             }
+        }
 
-            Self::set_file_permissions(&zip_file, &output_path)?;
+        let mut zip = ZipWriter::new(File::create(output_path)?);
+        for (archive_idx, entry_idx, output_name) in plan {
+            let archive = &mut archives[archive_idx];
+            let raw_file = archive.by_index_raw(entry_idx)?;
+            zip.raw_copy_file_rename(raw_file, &output_name)?;
         }
-        log!("[unzip] extracted {} files.", archive.len());
+        zip.finish()?;
         Ok(())
     }
 
+    fn renamed(name: &str, n: usize) -> String {
+        match name.rsplit_once('.') {
+            Some((stem, ext)) => format!("{stem} ({n}).{ext}"),
+            None => format!("{name} ({n})"),
+        }
+    }
+
     fn log_comment(file_num: usize, zip_file: &ZipFile) {
         let comment = zip_file.comment();
         if !comment.is_empty() {
@@ -1,4 +1,5 @@
 ///
+use globset::Error as GlobError;
 use ioe::IoError;
 use std::io;
 use std::path::StripPrefixError;
@@ -13,6 +14,14 @@ pub enum ZippyError {
     StripPathPrefixError,
     WalkDirError(WalkDirError),
     ZipError(ZipError),
+    /// The supplied `--password` didn't match the one the entry was
+    /// encrypted with (or the entry's checksum didn't validate after
+    /// decryption).
+    IncorrectPassword,
+    GlobError(GlobError),
+    /// `auto` was given a mix of zip and non-zip inputs, so it's unclear
+    /// whether to compress or decompress them.
+    AmbiguousOperation,
 }
 
 impl From<IoError> for ZippyError {
@@ -44,3 +53,9 @@ impl From<ZipError> for ZippyError {
         ZippyError::ZipError(err)
     }
 }
+
+impl From<GlobError> for ZippyError {
+    fn from(err: GlobError) -> ZippyError {
+        ZippyError::GlobError(err)
+    }
+}